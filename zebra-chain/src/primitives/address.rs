@@ -1,13 +1,36 @@
 //! `zcash_address` conversion to `zebra_chain` address types.
 //!
 //! Usage: <https://docs.rs/zcash_address/0.2.0/zcash_address/trait.TryFromAddress.html#examples>
+//!
+//! ZIP 316 "Revision 1" reserves a typecode range for unified address metadata items, including
+//! an expiry height and expiry time. As of this writing no such typecodes have been published,
+//! so this module does not implement expiry-metadata parsing or `has_expired_by_*` accessors:
+//! doing so would mean hard-coding guessed typecodes that could never interoperate, and could
+//! collide with whatever typecodes are eventually assigned. This is intentionally left
+//! unimplemented and blocked on ZIP 316 Revision 1 being finalized, rather than merged under
+//! placeholder values; see [`unified::Receiver::Unknown`] below, which rejects every such item
+//! until then.
 
 use zcash_address::unified::{self, Container, Receiver};
 use zcash_primitives::sapling;
 
 use crate::{parameters::NetworkKind, transparent, BoxError};
 
+/// The Zcash value pools that a [`Address`] can receive funds in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolType {
+    /// The transparent pool.
+    Transparent,
+
+    /// The Sapling shielded pool.
+    Sapling,
+
+    /// The Orchard shielded pool.
+    Orchard,
+}
+
 /// Zcash address variants
+#[derive(Clone, Debug)]
 pub enum Address {
     /// Sapling address
     Sapling {
@@ -31,7 +54,14 @@ pub enum Address {
 
         /// Sapling address
         sapling: Option<sapling::PaymentAddress>,
+
+        /// Transparent address, present when the unified address has a
+        /// transparent-capable (P2PKH/P2SH) receiver item.
+        transparent: Option<transparent::Address>,
     },
+
+    /// Transparent address
+    Transparent(transparent::Address),
 }
 
 impl zcash_address::TryFromAddress for Address {
@@ -48,6 +78,26 @@ impl zcash_address::TryFromAddress for Address {
             .ok_or_else(|| BoxError::from("not a valid sapling address").into())
     }
 
+    fn try_from_transparent_p2pkh(
+        network: zcash_address::Network,
+        data: [u8; 20],
+    ) -> Result<Self, zcash_address::ConversionError<Self::Error>> {
+        let network = NetworkKind::from_zcash_address(network);
+        Ok(Self::Transparent(transparent::Address::from_pub_key_hash(
+            network, data,
+        )))
+    }
+
+    fn try_from_transparent_p2sh(
+        network: zcash_address::Network,
+        data: [u8; 20],
+    ) -> Result<Self, zcash_address::ConversionError<Self::Error>> {
+        let network = NetworkKind::from_zcash_address(network);
+        Ok(Self::Transparent(transparent::Address::from_script_hash(
+            network, data,
+        )))
+    }
+
     fn try_from_unified(
         network: zcash_address::Network,
         unified_address: zcash_address::unified::Address,
@@ -55,6 +105,7 @@ impl zcash_address::TryFromAddress for Address {
         let network = NetworkKind::from_zcash_address(network);
         let mut orchard = None;
         let mut sapling = None;
+        let mut transparent = None;
 
         for receiver in unified_address.items().into_iter() {
             match receiver {
@@ -82,7 +133,19 @@ impl zcash_address::TryFromAddress for Address {
                         .into());
                     }
                 }
+                unified::Receiver::P2pkh(data) => {
+                    transparent = Some(transparent::Address::from_pub_key_hash(network, data));
+                }
+                unified::Receiver::P2sh(data) => {
+                    transparent = Some(transparent::Address::from_script_hash(network, data));
+                }
                 unified::Receiver::Unknown { .. } => {
+                    // ZIP 316 Revision 1 reserves a range of typecodes for metadata
+                    // items (e.g. expiry height/time), but no such typecodes have
+                    // been published yet, so every `Unknown` item is still rejected.
+                    //
+                    // TODO: recognize ZIP 316 Revision 1 metadata typecodes once they
+                    // are specified, instead of rejecting them here.
                     return Err(BoxError::from("Unsupported receiver in a Unified Address.").into());
                 }
                 _ => {}
@@ -94,6 +157,7 @@ impl zcash_address::TryFromAddress for Address {
             unified_address,
             orchard,
             sapling,
+            transparent,
         })
     }
 }
@@ -103,19 +167,43 @@ impl Address {
     pub fn network(&self) -> NetworkKind {
         match &self {
             Self::Sapling { network, .. } | Self::Unified { network, .. } => *network,
+            Self::Transparent(address) => address.network_kind(),
         }
     }
 
     /// Returns true if the address is PayToScriptHash
     /// Returns false if the address is PayToPublicKeyHash or shielded.
     pub fn is_script_hash(&self) -> bool {
-        match &self {
-            Self::Sapling { .. } | Self::Unified { .. } => false,
-            _ => true
+        matches!(
+            self,
+            Self::Transparent(transparent::Address::PayToScriptHash { .. })
+        )
+    }
+
+    /// Returns the transparent receiver contained in this address, if any.
+    ///
+    /// For [`Address::Transparent`], returns the address itself. For
+    /// [`Address::Unified`], returns the P2PKH/P2SH transparent receiver item
+    /// the unified address contains, if any. Returns `None` for shielded-only
+    /// addresses.
+    pub fn transparent_receiver(&self) -> Option<transparent::Address> {
+        match self {
+            Self::Transparent(address) => Some(address.clone()),
+            Self::Unified { transparent, .. } => transparent.clone(),
+            Self::Sapling { .. } => None,
         }
     }
 
-    /// Returns the payment address for transparent or sapling addresses.
+    /// Returns `true` if `address` is the transparent receiver contained in this address.
+    ///
+    /// This lets callers expand a unified address into the transparent address
+    /// that the address index actually stores, then filter query results by
+    /// the originally requested unified address.
+    pub fn matches_receiver(&self, address: &transparent::Address) -> bool {
+        self.transparent_receiver().as_ref() == Some(address)
+    }
+
+    /// Returns the payment address for transparent, sapling, or unified addresses.
     pub fn payment_address(&self) -> Option<String> {
         use zcash_address::{ToAddress, ZcashAddress};
 
@@ -125,9 +213,74 @@ impl Address {
                 let address = ZcashAddress::from_sapling(network.to_zcash_address(), data);
                 Some(address.encode())
             }
-            Self::Unified { .. } => None,
+            Self::Transparent(address) => Some(address.to_string()),
+            Self::Unified {
+                network,
+                unified_address,
+                ..
+            } => {
+                let address =
+                    ZcashAddress::from_unified(network.to_zcash_address(), unified_address.clone());
+                Some(address.encode())
+            }
         }
     }
+
+    /// Returns each constituent receiver of this address, re-encoded as its own
+    /// canonical single-pool [`ZcashAddress`](zcash_address::ZcashAddress) string.
+    ///
+    /// For [`Address::Transparent`] and [`Address::Sapling`], returns a single
+    /// receiver. For [`Address::Unified`], returns one receiver per pool the
+    /// unified address can receive in; an Orchard receiver has no standalone
+    /// encoding, so it is re-encoded as a unified address containing only
+    /// that item.
+    pub fn receivers(&self) -> Vec<(PoolType, String)> {
+        use zcash_address::{ToAddress, ZcashAddress};
+
+        match self {
+            Self::Transparent(address) => vec![(PoolType::Transparent, address.to_string())],
+            Self::Sapling { address, network } => {
+                let data = address.to_bytes();
+                let address = ZcashAddress::from_sapling(network.to_zcash_address(), data);
+                vec![(PoolType::Sapling, address.encode())]
+            }
+            Self::Unified {
+                network,
+                transparent,
+                sapling,
+                orchard,
+                ..
+            } => {
+                let zcash_network = network.to_zcash_address();
+                let mut receivers = Vec::new();
+
+                if let Some(address) = transparent {
+                    receivers.push((PoolType::Transparent, address.to_string()));
+                }
+
+                if let Some(address) = sapling {
+                    let data = address.to_bytes();
+                    let address = ZcashAddress::from_sapling(zcash_network, data);
+                    receivers.push((PoolType::Sapling, address.encode()));
+                }
+
+                if let Some(address) = orchard {
+                    let item = unified::Receiver::Orchard(address.to_raw_address_bytes());
+                    let unified_address = unified::Address::try_from_items(vec![item])
+                        .expect("a single Orchard receiver is always a valid unified address");
+                    let address = ZcashAddress::from_unified(zcash_network, unified_address);
+                    receivers.push((PoolType::Orchard, address.encode()));
+                }
+
+                receivers
+            }
+        }
+    }
+
+    /// Returns `true` if this address has a receiver in `pool`.
+    pub fn has_receiver_of_type(&self, pool: PoolType) -> bool {
+        self.receivers().iter().any(|(item, _)| *item == pool)
+    }
 }
 
 impl NetworkKind {
@@ -153,3 +306,122 @@ impl NetworkKind {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use zcash_address::{unified, ToAddress, ZcashAddress};
+
+    use super::*;
+
+    /// Returns a valid testnet Sapling [`sapling::PaymentAddress`], derived deterministically
+    /// from `seed`.
+    fn sapling_test_address(seed: u8) -> sapling::PaymentAddress {
+        use zcash_primitives::zip32::ExtendedSpendingKey;
+
+        let extended_spending_key = ExtendedSpendingKey::master(&[seed; 32]);
+        extended_spending_key.default_address().1
+    }
+
+    fn transparent_test_address(seed: u8) -> transparent::Address {
+        transparent::Address::from_pub_key_hash(NetworkKind::Testnet, [seed; 20])
+    }
+
+    #[test]
+    fn is_script_hash_distinguishes_p2sh_from_p2pkh() {
+        let p2pkh = Address::Transparent(transparent::Address::from_pub_key_hash(
+            NetworkKind::Testnet,
+            [1; 20],
+        ));
+        let p2sh = Address::Transparent(transparent::Address::from_script_hash(
+            NetworkKind::Testnet,
+            [1; 20],
+        ));
+
+        assert!(!p2pkh.is_script_hash());
+        assert!(p2sh.is_script_hash());
+    }
+
+    #[test]
+    fn transparent_receiver_and_matches_receiver() {
+        let transparent_address = transparent_test_address(1);
+        let other_address = transparent_test_address(2);
+
+        let address = Address::Transparent(transparent_address.clone());
+        assert_eq!(address.transparent_receiver(), Some(transparent_address.clone()));
+        assert!(address.matches_receiver(&transparent_address));
+        assert!(!address.matches_receiver(&other_address));
+
+        let sapling_only = Address::Sapling {
+            network: NetworkKind::Testnet,
+            address: sapling_test_address(1),
+        };
+        assert_eq!(sapling_only.transparent_receiver(), None);
+        assert!(!sapling_only.matches_receiver(&transparent_address));
+    }
+
+    #[test]
+    fn try_from_unified_accepts_transparent_and_shielded_receivers() {
+        let payment_address = sapling_test_address(1);
+        let pub_key_hash = [1; 20];
+        let transparent_address =
+            transparent::Address::from_pub_key_hash(NetworkKind::Testnet, pub_key_hash);
+
+        let unified_address = unified::Address::try_from_items(vec![
+            unified::Receiver::Sapling(payment_address.to_bytes()),
+            unified::Receiver::P2pkh(pub_key_hash),
+        ])
+        .expect("a Sapling + P2PKH unified address is valid");
+
+        let encoded = ZcashAddress::from_unified(zcash_address::Network::Test, unified_address);
+        let address = encoded
+            .convert::<Address>()
+            .expect("a valid Sapling + P2PKH unified address should convert");
+
+        assert_eq!(address.transparent_receiver(), Some(transparent_address));
+        assert!(address.has_receiver_of_type(PoolType::Sapling));
+        assert!(address.has_receiver_of_type(PoolType::Transparent));
+        assert!(!address.has_receiver_of_type(PoolType::Orchard));
+    }
+
+    #[test]
+    fn try_from_unified_rejects_unknown_receiver() {
+        let payment_address = sapling_test_address(1);
+
+        let unified_address = unified::Address::try_from_items(vec![
+            unified::Receiver::Sapling(payment_address.to_bytes()),
+            unified::Receiver::Unknown {
+                typecode: 0xff,
+                data: vec![0],
+            },
+        ])
+        .expect("a Sapling receiver plus an unrecognized item is still well-formed");
+
+        let encoded = ZcashAddress::from_unified(zcash_address::Network::Test, unified_address);
+
+        assert!(encoded.convert::<Address>().is_err());
+    }
+
+    #[test]
+    fn receivers_lists_one_entry_per_pool() {
+        let payment_address = sapling_test_address(1);
+        let transparent_address = transparent_test_address(1);
+
+        let address = Address::Unified {
+            network: NetworkKind::Testnet,
+            unified_address: unified::Address::try_from_items(vec![
+                unified::Receiver::Sapling(payment_address.to_bytes()),
+            ])
+            .expect("a single Sapling receiver is a valid unified address"),
+            orchard: None,
+            sapling: Some(payment_address),
+            transparent: Some(transparent_address),
+        };
+
+        let receivers = address.receivers();
+        assert_eq!(receivers.len(), 2);
+        assert!(receivers
+            .iter()
+            .any(|(pool, _)| *pool == PoolType::Transparent));
+        assert!(receivers.iter().any(|(pool, _)| *pool == PoolType::Sapling));
+    }
+}
@@ -0,0 +1,586 @@
+//! ZIP 321 payment request URI parsing and encoding.
+//!
+//! <https://zips.z.cash/zip-0321>
+
+use std::fmt;
+
+use zcash_address::ZcashAddress;
+
+use crate::amount::{Amount, NonNegative};
+
+use super::address::{Address, PoolType};
+
+/// The URI scheme used by ZIP 321 payment requests.
+const URI_SCHEME: &str = "zcash:";
+
+/// The maximum number of decimal places allowed in an `amount` parameter.
+const MAX_AMOUNT_DECIMALS: u32 = 8;
+
+/// An error that occurred while parsing a ZIP 321 payment request URI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The URI did not start with the `zcash:` scheme.
+    MissingScheme,
+
+    /// A query parameter's index was not a valid non-negative integer.
+    InvalidIndex(String),
+
+    /// Parameter index `0` was given explicitly, rather than by omitting the index.
+    ExplicitZeroIndex(String),
+
+    /// The same parameter was given more than once for the same payment index.
+    DuplicateParameter(String, usize),
+
+    /// The payment indices are not contiguous, starting at `0`.
+    NonContiguousIndices,
+
+    /// A payment is missing its required `address` parameter.
+    MissingAddress(usize),
+
+    /// A payment is missing its required `amount` parameter.
+    MissingAmount(usize),
+
+    /// An `address` parameter could not be parsed as a Zcash address.
+    InvalidAddress(usize),
+
+    /// An `amount` parameter was not a valid non-negative decimal amount.
+    InvalidAmount(usize),
+
+    /// An `amount` parameter had more than [`MAX_AMOUNT_DECIMALS`] decimal places.
+    TooManyDecimals(usize),
+
+    /// A `memo` parameter was attached to a transparent recipient.
+    MemoOnTransparentRecipient(usize),
+
+    /// A `memo` parameter was not validly base64url-encoded.
+    InvalidMemo(usize),
+
+    /// A percent-encoded parameter value contained invalid escapes or UTF-8.
+    InvalidPercentEncoding(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingScheme => write!(f, "payment request URI must start with 'zcash:'"),
+            ParseError::InvalidIndex(key) => write!(f, "invalid parameter index in '{key}'"),
+            ParseError::ExplicitZeroIndex(key) => {
+                write!(f, "index 0 must be expressed by omitting the index in '{key}'")
+            }
+            ParseError::DuplicateParameter(name, index) => {
+                write!(f, "duplicate '{name}' parameter for payment {index}")
+            }
+            ParseError::NonContiguousIndices => {
+                write!(f, "payment indices must be contiguous, starting at 0")
+            }
+            ParseError::MissingAddress(index) => {
+                write!(f, "payment {index} is missing a required 'address' parameter")
+            }
+            ParseError::MissingAmount(index) => {
+                write!(f, "payment {index} is missing a required 'amount' parameter")
+            }
+            ParseError::InvalidAddress(index) => {
+                write!(f, "payment {index} has an invalid 'address' parameter")
+            }
+            ParseError::InvalidAmount(index) => {
+                write!(f, "payment {index} has an invalid 'amount' parameter")
+            }
+            ParseError::TooManyDecimals(index) => write!(
+                f,
+                "payment {index}'s 'amount' has more than {MAX_AMOUNT_DECIMALS} decimal places"
+            ),
+            ParseError::MemoOnTransparentRecipient(index) => write!(
+                f,
+                "payment {index} has a 'memo', but its recipient is a transparent address"
+            ),
+            ParseError::InvalidMemo(index) => {
+                write!(f, "payment {index} has an invalid base64url-encoded 'memo'")
+            }
+            ParseError::InvalidPercentEncoding(value) => {
+                write!(f, "invalid percent-encoding in '{value}'")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A single payment within a ZIP 321 [`PaymentRequest`].
+#[derive(Clone, Debug)]
+pub struct Payment {
+    /// The recipient of this payment.
+    pub address: Address,
+
+    /// The amount to send to [`Self::address`].
+    pub amount: Amount<NonNegative>,
+
+    /// An optional memo, only valid when `address` is shielded.
+    pub memo: Option<Vec<u8>>,
+
+    /// An optional human-readable label for the recipient.
+    pub label: Option<String>,
+
+    /// An optional human-readable message describing the purpose of the payment.
+    pub message: Option<String>,
+}
+
+/// A parsed ZIP 321 payment request, as carried by a `zcash:` URI.
+#[derive(Clone, Debug)]
+pub struct PaymentRequest {
+    /// The payments making up this request, in index order.
+    pub payments: Vec<Payment>,
+}
+
+impl PaymentRequest {
+    /// Parses a ZIP 321 payment request URI.
+    pub fn parse(uri: &str) -> Result<Self, ParseError> {
+        let body = uri.strip_prefix(URI_SCHEME).ok_or(ParseError::MissingScheme)?;
+
+        let (leading_address, query) = match body.find('?') {
+            Some(pos) => (&body[..pos], &body[pos + 1..]),
+            None => (body, ""),
+        };
+
+        // Collect each parameter, keyed by (base name, index), rejecting duplicates
+        // and explicit index 0 along the way.
+        let mut by_index: Vec<(usize, Vec<(String, String)>)> = Vec::new();
+
+        let mut insert = |base: String, index: usize, value: String| -> Result<(), ParseError> {
+            if let Some(pos) = by_index.iter().position(|(i, _)| *i == index) {
+                if by_index[pos].1.iter().any(|(name, _)| *name == base) {
+                    return Err(ParseError::DuplicateParameter(base, index));
+                }
+                by_index[pos].1.push((base, value));
+            } else {
+                by_index.push((index, vec![(base, value)]));
+            }
+            Ok(())
+        };
+
+        if !leading_address.is_empty() {
+            insert(
+                "address".to_string(),
+                0,
+                percent_decode(leading_address)?,
+            )?;
+        }
+
+        if !query.is_empty() {
+            for pair in query.split('&') {
+                if pair.is_empty() {
+                    continue;
+                }
+
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                let value = percent_decode(value)?;
+
+                let (base, index) = match key.rsplit_once('.') {
+                    Some((base, index_str)) => {
+                        let index: usize = index_str
+                            .parse()
+                            .map_err(|_| ParseError::InvalidIndex(key.to_string()))?;
+                        if index == 0 {
+                            return Err(ParseError::ExplicitZeroIndex(key.to_string()));
+                        }
+                        (base.to_string(), index)
+                    }
+                    None => (key.to_string(), 0),
+                };
+
+                insert(base, index, value)?;
+            }
+        }
+
+        by_index.sort_by_key(|(index, _)| *index);
+        for (expected, (index, _)) in by_index.iter().enumerate() {
+            if expected != *index {
+                return Err(ParseError::NonContiguousIndices);
+            }
+        }
+
+        let payments = by_index
+            .into_iter()
+            .map(|(index, params)| parse_payment(index, &params))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(PaymentRequest { payments })
+    }
+
+    /// Encodes this payment request as a canonical ZIP 321 `zcash:` URI.
+    pub fn encode(&self) -> String {
+        let mut uri = String::from(URI_SCHEME);
+
+        for (index, payment) in self.payments.iter().enumerate() {
+            if index == 0 {
+                uri.push_str(&percent_encode(&encode_address(&payment.address)));
+                uri.push('?');
+                uri.push_str(&format!("amount={}", encode_zec_amount(payment.amount)));
+            } else {
+                uri.push_str(&format!(
+                    "&address.{index}={}",
+                    percent_encode(&encode_address(&payment.address))
+                ));
+                uri.push_str(&format!(
+                    "&amount.{index}={}",
+                    encode_zec_amount(payment.amount)
+                ));
+            }
+
+            let suffix = if index == 0 {
+                String::new()
+            } else {
+                format!(".{index}")
+            };
+
+            if let Some(memo) = &payment.memo {
+                uri.push_str(&format!("&memo{suffix}={}", base64url_encode(memo)));
+            }
+
+            if let Some(label) = &payment.label {
+                uri.push_str(&format!("&label{suffix}={}", percent_encode(label)));
+            }
+
+            if let Some(message) = &payment.message {
+                uri.push_str(&format!("&message{suffix}={}", percent_encode(message)));
+            }
+        }
+
+        uri
+    }
+}
+
+/// Parses a single payment's collected `(name, value)` parameters.
+fn parse_payment(index: usize, params: &[(String, String)]) -> Result<Payment, ParseError> {
+    let get = |name: &str| params.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+
+    let address_str = get("address").ok_or(ParseError::MissingAddress(index))?;
+    let address = ZcashAddress::try_from_encoded(address_str)
+        .map_err(|_| ParseError::InvalidAddress(index))?
+        .convert::<Address>()
+        .map_err(|_| ParseError::InvalidAddress(index))?;
+
+    let amount_str = get("amount").ok_or(ParseError::MissingAmount(index))?;
+    let amount = parse_zec_amount(amount_str, index)?;
+
+    let memo = match get("memo") {
+        Some(memo_str) => {
+            let has_shielded_receiver = address.has_receiver_of_type(PoolType::Sapling)
+                || address.has_receiver_of_type(PoolType::Orchard);
+            if !has_shielded_receiver {
+                return Err(ParseError::MemoOnTransparentRecipient(index));
+            }
+            Some(base64url_decode(memo_str).ok_or(ParseError::InvalidMemo(index))?)
+        }
+        None => None,
+    };
+
+    Ok(Payment {
+        address,
+        amount,
+        memo,
+        label: get("label").map(str::to_string),
+        message: get("message").map(str::to_string),
+    })
+}
+
+/// Parses a decimal ZEC amount (e.g. `"1.234"`) into [`Amount<NonNegative>`] zatoshis.
+fn parse_zec_amount(s: &str, index: usize) -> Result<Amount<NonNegative>, ParseError> {
+    let (whole, frac) = match s.split_once('.') {
+        Some((whole, frac)) => (whole, frac),
+        None => (s, ""),
+    };
+
+    if frac.len() as u32 > MAX_AMOUNT_DECIMALS {
+        return Err(ParseError::TooManyDecimals(index));
+    }
+
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| ParseError::InvalidAmount(index))?
+    };
+
+    let mut frac_digits = frac.to_string();
+    while frac_digits.len() < MAX_AMOUNT_DECIMALS as usize {
+        frac_digits.push('0');
+    }
+    let frac: u64 = if frac_digits.is_empty() {
+        0
+    } else {
+        frac_digits
+            .parse()
+            .map_err(|_| ParseError::InvalidAmount(index))?
+    };
+
+    let zatoshis = whole
+        .checked_mul(10u64.pow(MAX_AMOUNT_DECIMALS))
+        .and_then(|z| z.checked_add(frac))
+        .ok_or(ParseError::InvalidAmount(index))?;
+
+    i64::try_from(zatoshis)
+        .ok()
+        .and_then(|zatoshis| Amount::try_from(zatoshis).ok())
+        .ok_or(ParseError::InvalidAmount(index))
+}
+
+/// Encodes an amount in zatoshis as a canonical decimal ZEC string.
+fn encode_zec_amount(amount: Amount<NonNegative>) -> String {
+    let zatoshis = i64::from(amount) as u64;
+    let whole = zatoshis / 10u64.pow(MAX_AMOUNT_DECIMALS);
+    let frac = zatoshis % 10u64.pow(MAX_AMOUNT_DECIMALS);
+
+    if frac == 0 {
+        whole.to_string()
+    } else {
+        let frac_str = format!("{frac:08}");
+        let frac_str = frac_str.trim_end_matches('0');
+        format!("{whole}.{frac_str}")
+    }
+}
+
+/// Encodes `address` as its canonical address string.
+fn encode_address(address: &Address) -> String {
+    address
+        .payment_address()
+        .expect("every Address variant has a canonical encoding")
+}
+
+/// Percent-encodes `input`, leaving RFC 3986 unreserved characters untouched.
+fn percent_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+/// Decodes a percent-encoded query-string component.
+fn percent_decode(input: &str) -> Result<String, ParseError> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = input
+                .get(i + 1..i + 3)
+                .ok_or_else(|| ParseError::InvalidPercentEncoding(input.to_string()))?;
+            let byte = u8::from_str_radix(hex, 16)
+                .map_err(|_| ParseError::InvalidPercentEncoding(input.to_string()))?;
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(out).map_err(|_| ParseError::InvalidPercentEncoding(input.to_string()))
+}
+
+/// The unpadded base64url alphabet, as used by ZIP 321 `memo` parameters.
+const BASE64URL_ALPHABET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes `data` using unpadded base64url, per ZIP 321's `memo` encoding.
+fn base64url_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b0 = chunk[0] as u32;
+        let b1 = chunk.get(1).copied().unwrap_or(0) as u32;
+        let b2 = chunk.get(2).copied().unwrap_or(0) as u32;
+        let n = (b0 << 16) | (b1 << 8) | b2;
+
+        out.push(BASE64URL_ALPHABET[((n >> 18) & 0x3f) as usize] as char);
+        out.push(BASE64URL_ALPHABET[((n >> 12) & 0x3f) as usize] as char);
+        if chunk.len() > 1 {
+            out.push(BASE64URL_ALPHABET[((n >> 6) & 0x3f) as usize] as char);
+        }
+        if chunk.len() > 2 {
+            out.push(BASE64URL_ALPHABET[(n & 0x3f) as usize] as char);
+        }
+    }
+    out
+}
+
+/// Decodes unpadded base64url, per ZIP 321's `memo` encoding.
+///
+/// Returns `None` if `s` contains characters outside the base64url alphabet.
+fn base64url_decode(s: &str) -> Option<Vec<u8>> {
+    let values = s
+        .bytes()
+        .map(|b| {
+            BASE64URL_ALPHABET
+                .iter()
+                .position(|&a| a == b)
+                .map(|pos| pos as u32)
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    // A trailing chunk of a single symbol can't encode a whole byte: unpadded
+    // base64 groups carry 2, 3, or 4 symbols per 1, 2, or 3 decoded bytes.
+    if values.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(values.len() * 3 / 4);
+    for chunk in values.chunks(4) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, v)| acc | (v << (18 - 6 * i)));
+
+        out.push((n >> 16) as u8);
+        if chunk.len() > 2 {
+            out.push((n >> 8) as u8);
+        }
+        if chunk.len() > 3 {
+            out.push(n as u8);
+        }
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_address::{Network, ToAddress, ZcashAddress};
+
+    use super::*;
+
+    /// Returns a canonical, checksummed testnet transparent P2PKH address string.
+    fn test_address(seed: u8) -> String {
+        ZcashAddress::from_transparent_p2pkh(Network::Test, [seed; 20]).encode()
+    }
+
+    /// Returns a valid testnet Sapling address string, derived deterministically from `seed`.
+    ///
+    /// Sapling addresses encode a diversifier and a Jubjub curve point, so unlike transparent
+    /// addresses they can't be built from arbitrary bytes; a real address is derived from a
+    /// spending key instead.
+    fn shielded_test_address(seed: u8) -> String {
+        use zcash_primitives::zip32::ExtendedSpendingKey;
+
+        let extended_spending_key = ExtendedSpendingKey::master(&[seed; 32]);
+        let (_, payment_address) = extended_spending_key.default_address();
+
+        ZcashAddress::from_sapling(Network::Test, payment_address.to_bytes()).encode()
+    }
+
+    #[test]
+    fn parses_shorthand_single_recipient() {
+        let address = test_address(1);
+        let uri = format!("zcash:{address}?amount=1.234");
+
+        let request = PaymentRequest::parse(&uri).expect("valid shorthand URI should parse");
+
+        assert_eq!(request.payments.len(), 1);
+        let payment = &request.payments[0];
+        assert_eq!(payment.amount, Amount::try_from(123_400_000i64).unwrap());
+        assert!(payment.memo.is_none());
+        assert!(payment.label.is_none());
+        assert!(payment.message.is_none());
+    }
+
+    #[test]
+    fn parses_indexed_multi_recipient() {
+        let first = test_address(1);
+        let second = test_address(2);
+        let uri = format!("zcash:{first}?amount=1&address.1={second}&amount.1=2.5&label.1=gift");
+
+        let request = PaymentRequest::parse(&uri).expect("valid indexed URI should parse");
+
+        assert_eq!(request.payments.len(), 2);
+        assert_eq!(request.payments[1].label.as_deref(), Some("gift"));
+    }
+
+    #[test]
+    fn round_trips_indexed_multi_recipient() {
+        let first = test_address(1);
+        let second = test_address(2);
+        let uri = format!("zcash:{first}?amount=1&address.1={second}&amount.1=2.5");
+
+        let request = PaymentRequest::parse(&uri).expect("valid indexed URI should parse");
+        let reencoded = request.encode();
+
+        assert_eq!(reencoded, uri);
+        assert_eq!(
+            PaymentRequest::parse(&reencoded)
+                .expect("re-encoded URI should parse")
+                .payments
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_parameter() {
+        let address = test_address(1);
+        let uri = format!("zcash:{address}?amount=1&amount=2");
+
+        assert!(matches!(
+            PaymentRequest::parse(&uri),
+            Err(ParseError::DuplicateParameter(name, 0)) if name == "amount"
+        ));
+    }
+
+    #[test]
+    fn rejects_explicit_index_zero() {
+        let address = test_address(1);
+        let uri = format!("zcash:{address}?amount=1&amount.0=2");
+
+        assert!(matches!(
+            PaymentRequest::parse(&uri),
+            Err(ParseError::ExplicitZeroIndex(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_non_contiguous_indices() {
+        let first = test_address(1);
+        let second = test_address(2);
+        let uri = format!("zcash:{first}?amount=1&address.2={second}&amount.2=2");
+
+        assert!(matches!(
+            PaymentRequest::parse(&uri),
+            Err(ParseError::NonContiguousIndices)
+        ));
+    }
+
+    #[test]
+    fn rejects_memo_on_transparent_recipient() {
+        let address = test_address(1);
+        let uri = format!("zcash:{address}?amount=1&memo=aGVsbG8");
+
+        assert!(matches!(
+            PaymentRequest::parse(&uri),
+            Err(ParseError::MemoOnTransparentRecipient(0))
+        ));
+    }
+
+    #[test]
+    fn round_trips_memo_on_shielded_recipient() {
+        let address = shielded_test_address(1);
+        let uri = format!("zcash:{address}?amount=1&memo=aGVsbG8");
+
+        let request = PaymentRequest::parse(&uri).expect("memo on a shielded recipient is valid");
+
+        assert_eq!(request.payments[0].memo.as_deref(), Some(b"hello".as_slice()));
+        assert_eq!(request.encode(), uri);
+    }
+
+    #[test]
+    fn rejects_amount_with_too_many_decimals() {
+        let address = test_address(1);
+        let uri = format!("zcash:{address}?amount=1.123456789");
+
+        assert!(matches!(
+            PaymentRequest::parse(&uri),
+            Err(ParseError::TooManyDecimals(0))
+        ));
+    }
+}
@@ -16,7 +16,9 @@ use std::{
     ops::RangeInclusive,
 };
 
-use zebra_chain::{block::Height, parameters::Network, transaction, transparent};
+use zebra_chain::{
+    block::Height, parameters::Network, primitives::address::Address, transaction, transparent,
+};
 
 use crate::{
     service::finalized_state::ZebraDb,
@@ -90,12 +92,18 @@ impl AddressUtxos {
 /// Returns the unspent transparent outputs (UTXOs) for `addresses` in the finalized chain,
 /// and the finalized tip heights the UTXOs were queried at.
 ///
+/// Each [`Address::Unified`] in `addresses` is expanded to its transparent receiver, if it has
+/// one, before querying the index; shielded-only addresses, and unified addresses with no
+/// transparent receiver, contribute nothing to the query. This lets RPCs such as
+/// `getaddressutxos`/`getaddressbalance` pass a unified address straight through to this
+/// function, even though the index itself is only ever keyed by transparent address.
+///
 /// If the addresses do not exist in the finalized `db`, returns an empty list.
 //
 // TODO: turn the return type into a struct?
 fn finalized_address_utxos(
     db: &ZebraDb,
-    addresses: &HashSet<transparent::Address>,
+    addresses: &[Address],
 ) -> (
     BTreeMap<OutputLocation, transparent::Output>,
     Option<RangeInclusive<Height>>,
@@ -104,10 +112,15 @@ fn finalized_address_utxos(
     //
     // The StateService can commit additional blocks while we are querying address UTXOs.
 
+    let transparent_addresses: HashSet<transparent::Address> = addresses
+        .iter()
+        .filter_map(Address::transparent_receiver)
+        .collect();
+
     // Check if the finalized state changed while we were querying it
     let start_finalized_tip = db.finalized_tip_height();
 
-    let finalized_utxos = db.partial_finalized_address_utxos(addresses);
+    let finalized_utxos = db.partial_finalized_address_utxos(&transparent_addresses);
 
     let end_finalized_tip = db.finalized_tip_height();
 
@@ -138,3 +151,35 @@ fn apply_utxo_changes(
         .filter(|(utxo_location, _output)| !spent_chain_utxos.contains(utxo_location))
         .collect()
 }
+
+/// Returns the combined finalized and non-finalized UTXOs for `addresses`, as an [`AddressUtxos`].
+///
+/// Expands any [`Address::Unified`] in `addresses` to its transparent receiver before querying
+/// the finalized index (which is only ever keyed by transparent address), combines the result
+/// with the supplied non-finalized `created_chain_utxos` and `spent_chain_utxos`, then filters
+/// the combined result back down with [`Address::matches_receiver`] so the caller gets back
+/// exactly the outputs sent to the unified (or transparent) addresses it asked for.
+pub fn address_utxos(
+    db: &ZebraDb,
+    network: &Network,
+    addresses: &[Address],
+    created_chain_utxos: BTreeMap<OutputLocation, transparent::Output>,
+    spent_chain_utxos: BTreeSet<OutputLocation>,
+    tx_ids: BTreeMap<TransactionLocation, transaction::Hash>,
+) -> AddressUtxos {
+    let (finalized_utxos, _finalized_tip_range) = finalized_address_utxos(db, addresses);
+    let combined_utxos = apply_utxo_changes(finalized_utxos, created_chain_utxos, spent_chain_utxos);
+
+    let matching_utxos = combined_utxos
+        .into_iter()
+        .filter(|(_utxo_location, output)| {
+            output.address(network).is_some_and(|output_address| {
+                addresses
+                    .iter()
+                    .any(|requested| requested.matches_receiver(&output_address))
+            })
+        })
+        .collect();
+
+    AddressUtxos::new(network, matching_utxos, tx_ids)
+}